@@ -0,0 +1,201 @@
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use hmac::{Hmac, Mac};
+use jwt::{SignWithKey, VerifyWithKey};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{DbPool, INTERNAL_ERROR, OK_RESPONSE, UNAUTHORIZED};
+
+#[derive(Serialize, Deserialize)]
+struct RegisterRequest {
+    email: String,
+    password: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LoginRequest {
+    email: String,
+    password: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthResponse {
+    token: String,
+}
+
+//claims carried by a verified JWT, scoped to the authenticated user
+pub struct Claims {
+    pub user_id: i32,
+}
+
+fn jwt_secret() -> String {
+    env::var("JWT_SECRET").expect("JWT_SECRET must be set")
+}
+
+fn jwt_maxage_secs() -> u64 {
+    env::var("JWT_MAXAGE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3600)
+}
+
+fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .unwrap()
+        .to_string()
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed_hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+fn generate_token(user_id: i32) -> String {
+    let key: Hmac<Sha256> = Hmac::new_from_slice(jwt_secret().as_bytes()).unwrap();
+    let exp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        + jwt_maxage_secs();
+
+    let mut claims = BTreeMap::new();
+    claims.insert("sub", user_id.to_string());
+    claims.insert("exp", exp.to_string());
+
+    claims.sign_with_key(&key).unwrap()
+}
+
+fn verify_token(token: &str) -> Option<Claims> {
+    let key: Hmac<Sha256> = Hmac::new_from_slice(jwt_secret().as_bytes()).ok()?;
+    let claims: BTreeMap<String, String> = token.verify_with_key(&key).ok()?;
+
+    let exp: u64 = claims.get("exp")?.parse().ok()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    if exp < now {
+        return None;
+    }
+
+    let user_id: i32 = claims.get("sub")?.parse().ok()?;
+    Some(Claims { user_id })
+}
+
+//pull the bearer token out of the raw request string's Authorization header
+fn extract_bearer_token(request: &str) -> Option<&str> {
+    request
+        .lines()
+        .find(|line| line.to_lowercase().starts_with("authorization:"))?
+        .split_whitespace()
+        .nth(2)
+}
+
+//verify the Authorization header on a request, returning the caller's claims
+pub fn authenticate(request: &str) -> Option<Claims> {
+    let token = extract_bearer_token(request)?;
+    verify_token(token)
+}
+
+//handle register request
+pub fn handle_register_request(request: &str, pool: &DbPool) -> (String, String) {
+    match (
+        serde_json::from_str::<RegisterRequest>(request.split("\r\n\r\n").last().unwrap_or_default()),
+        pool.get(),
+    ) {
+        (Ok(body), Ok(mut client)) => {
+            let password_hash = hash_password(&body.password);
+
+            match client.execute(
+                "INSERT INTO users (email, password_hash) VALUES ($1, $2)",
+                &[&body.email, &password_hash],
+            ) {
+                Ok(_) => (OK_RESPONSE.to_string(), "User registered".to_string()),
+                Err(_) => (INTERNAL_ERROR.to_string(), "Internal error".to_string()),
+            }
+        }
+        _ => (INTERNAL_ERROR.to_string(), "Internal error".to_string()),
+    }
+}
+
+//handle login request
+pub fn handle_login_request(request: &str, pool: &DbPool) -> (String, String) {
+    match (
+        serde_json::from_str::<LoginRequest>(request.split("\r\n\r\n").last().unwrap_or_default()),
+        pool.get(),
+    ) {
+        (Ok(body), Ok(mut client)) => {
+            match client.query_one(
+                "SELECT id, password_hash FROM users WHERE email = $1",
+                &[&body.email],
+            ) {
+                Ok(row) => {
+                    let user_id: i32 = row.get(0);
+                    let password_hash: String = row.get(1);
+
+                    if verify_password(&body.password, &password_hash) {
+                        let token = generate_token(user_id);
+                        (
+                            OK_RESPONSE.to_string(),
+                            serde_json::to_string(&AuthResponse { token }).unwrap(),
+                        )
+                    } else {
+                        (UNAUTHORIZED.to_string(), "Invalid credentials".to_string())
+                    }
+                }
+                Err(_) => (UNAUTHORIZED.to_string(), "Invalid credentials".to_string()),
+            }
+        }
+        _ => (INTERNAL_ERROR.to_string(), "Internal error".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_bearer_token_returns_the_token_not_the_scheme() {
+        let request = "GET /tasks HTTP/1.1\r\nAuthorization: Bearer abc.def.ghi\r\nHost: x";
+        assert_eq!(extract_bearer_token(request), Some("abc.def.ghi"));
+    }
+
+    #[test]
+    fn extract_bearer_token_is_case_insensitive_on_the_header_name() {
+        let request = "GET /tasks HTTP/1.1\r\nauthorization: Bearer abc.def.ghi\r\nHost: x";
+        assert_eq!(extract_bearer_token(request), Some("abc.def.ghi"));
+    }
+
+    #[test]
+    fn extract_bearer_token_is_none_when_header_is_missing() {
+        let request = "GET /tasks HTTP/1.1\r\nHost: x";
+        assert_eq!(extract_bearer_token(request), None);
+    }
+
+    #[test]
+    fn generate_token_round_trips_through_verify_token() {
+        std::env::set_var("JWT_SECRET", "test-secret");
+        let token = generate_token(42);
+        let claims = verify_token(&token).expect("token should verify");
+        assert_eq!(claims.user_id, 42);
+    }
+
+    #[test]
+    fn verify_token_rejects_garbage() {
+        std::env::set_var("JWT_SECRET", "test-secret");
+        assert!(verify_token("not-a-jwt").is_none());
+    }
+
+    #[test]
+    fn hash_password_round_trips_through_verify_password() {
+        let hash = hash_password("hunter2");
+        assert!(verify_password("hunter2", &hash));
+        assert!(!verify_password("wrong", &hash));
+    }
+}
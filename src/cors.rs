@@ -0,0 +1,60 @@
+use std::env;
+
+//which origin(s) may call this API; defaults to "*" so the binary works
+//unchanged for local dev and for a deployed frontend
+fn allowed_origin() -> String {
+    env::var("ALLOWED_ORIGIN").unwrap_or_else(|_| "*".to_string())
+}
+
+//answer a browser's CORS preflight for /tasks with the methods and
+//headers it's allowed to send; the Access-Control-Allow-Origin header
+//itself is added uniformly by `append_origin_header` like every other
+//response
+pub fn preflight_response() -> (String, String) {
+    let status_line = "HTTP/1.1 204 NO CONTENT\r\n\
+         Access-Control-Allow-Methods: GET, POST, PUT, DELETE, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Content-Type, Authorization\r\n\r\n"
+        .to_string();
+
+    (status_line, String::new())
+}
+
+//add Access-Control-Allow-Origin to any status line, so the browser
+//accepts the response whether it's a 200, 401, 404 or 500
+pub fn append_origin_header(status_line: &str) -> String {
+    match status_line.rfind("\r\n\r\n") {
+        Some(pos) => format!(
+            "{}\r\nAccess-Control-Allow-Origin: {}{}",
+            &status_line[..pos],
+            allowed_origin(),
+            &status_line[pos..]
+        ),
+        None => status_line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_origin_header_inserts_before_the_body_separator() {
+        let status_line = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n";
+
+        let with_cors = append_origin_header(status_line);
+
+        assert!(with_cors.contains("Access-Control-Allow-Origin:"));
+        assert!(with_cors.ends_with("\r\n\r\n"));
+
+        // the header line lands before the final blank line, not after it
+        let header_pos = with_cors.find("Access-Control-Allow-Origin:").unwrap();
+        let terminator_pos = with_cors.rfind("\r\n\r\n").unwrap();
+        assert!(header_pos < terminator_pos);
+    }
+
+    #[test]
+    fn append_origin_header_leaves_malformed_status_lines_untouched() {
+        let status_line = "HTTP/1.1 200 OK";
+        assert_eq!(append_origin_header(status_line), status_line);
+    }
+}
@@ -0,0 +1,116 @@
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+
+//headers only get so big for this API; anything past this is bogus
+const MAX_HEADER_BYTES: usize = 8 * 1024;
+
+//a fully-read HTTP/1.1 request: the request line + headers as sent, plus
+//exactly Content-Length bytes of body, read off the wire in full instead
+//of a single fixed-size read that can silently truncate a large body
+pub struct RawRequest {
+    head: String,
+    body: String,
+}
+
+impl RawRequest {
+    //the handlers already expect one big "head\r\n\r\nbody" string
+    pub fn as_str(&self) -> String {
+        format!("{}\r\n\r\n{}", self.head, self.body)
+    }
+
+    pub fn keep_alive(&self) -> bool {
+        !self
+            .head
+            .lines()
+            .any(|line| line.eq_ignore_ascii_case("Connection: close"))
+    }
+}
+
+//read one request off `stream`: headers first, then exactly
+//Content-Length bytes of body. Returns Ok(None) when the peer closed the
+//connection before sending a complete request head.
+pub async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<RawRequest>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 512];
+
+    let header_end = loop {
+        if let Some(pos) = find_header_end(&buffer) {
+            break pos;
+        }
+
+        if buffer.len() > MAX_HEADER_BYTES {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "request headers too large",
+            ));
+        }
+
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut body = buffer.split_off(header_end + 4);
+
+    let content_length = content_length(&head);
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "connection closed before the full body arrived",
+            ));
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok(Some(RawRequest {
+        head,
+        body: String::from_utf8_lossy(&body).to_string(),
+    }))
+}
+
+fn find_header_end(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(4).position(|w| w == b"\r\n\r\n")
+}
+
+fn content_length(head: &str) -> usize {
+    head.lines()
+        .find(|line| line.to_lowercase().starts_with("content-length:"))
+        .and_then(|line| line.split_once(':'))
+        .and_then(|(_, v)| v.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_header_end_locates_the_blank_line() {
+        let buffer = b"GET /tasks HTTP/1.1\r\nHost: x\r\n\r\nbody";
+        assert_eq!(find_header_end(buffer), Some(28));
+    }
+
+    #[test]
+    fn find_header_end_is_none_for_a_partial_read() {
+        let buffer = b"GET /tasks HTTP/1.1\r\nHost: x\r\n";
+        assert_eq!(find_header_end(buffer), None);
+    }
+
+    #[test]
+    fn content_length_reads_the_header_case_insensitively() {
+        let head = "POST /tasks HTTP/1.1\r\ncontent-length: 12\r\nHost: x";
+        assert_eq!(content_length(head), 12);
+    }
+
+    #[test]
+    fn content_length_defaults_to_zero_when_missing() {
+        let head = "GET /tasks HTTP/1.1\r\nHost: x";
+        assert_eq!(content_length(head), 0);
+    }
+}
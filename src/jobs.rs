@@ -0,0 +1,158 @@
+use serde_json::Value;
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{get_id, DbPool, INTERNAL_ERROR, NOT_FOUND, OK_RESPONSE};
+
+//how long the worker sleeps after each poll, whether or not it ran a job
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+//a unit of background work that can be serialized to JSON, enqueued, and
+//later replayed by a worker
+pub trait Runnable {
+    fn run(&self) -> Result<(), String>;
+    fn to_metadata(&self) -> Value;
+}
+
+struct ProcessTaskJob {
+    task_id: i32,
+}
+
+impl Runnable for ProcessTaskJob {
+    fn run(&self) -> Result<(), String> {
+        println!("Processing task {}", self.task_id);
+        Ok(())
+    }
+
+    fn to_metadata(&self) -> Value {
+        serde_json::json!({ "type": "process_task", "task_id": self.task_id })
+    }
+}
+
+fn from_metadata(metadata: &Value) -> Option<ProcessTaskJob> {
+    Some(ProcessTaskJob {
+        task_id: metadata.get("task_id")?.as_i64()? as i32,
+    })
+}
+
+//insert a job row in the `new` state for a worker to pick up later
+fn enqueue_job(pool: &DbPool, job: &dyn Runnable) -> Result<Uuid, Box<dyn std::error::Error>> {
+    let mut client = pool.get()?;
+    let id = Uuid::new_v4();
+
+    client.execute(
+        "INSERT INTO jobs (id, metadata, state) VALUES ($1, $2, 'new')",
+        &[&id, &job.to_metadata()],
+    )?;
+
+    Ok(id)
+}
+
+//run forever on its own thread, picking up the oldest pending job with
+//SELECT ... FOR UPDATE SKIP LOCKED so multiple workers never race on a row
+pub fn start_worker(pool: DbPool) {
+    thread::spawn(move || loop {
+        if let Err(e) = run_next_job(&pool) {
+            eprintln!("Job worker error: {}", e);
+        }
+        thread::sleep(POLL_INTERVAL);
+    });
+}
+
+fn run_next_job(pool: &DbPool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = pool.get()?;
+    let mut transaction = client.transaction()?;
+
+    let row = transaction.query_opt(
+        "SELECT id, metadata FROM jobs
+         WHERE state = 'new'
+         ORDER BY created_at ASC
+         FOR UPDATE SKIP LOCKED
+         LIMIT 1",
+        &[],
+    )?;
+
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(transaction.commit()?),
+    };
+
+    let id: Uuid = row.get(0);
+    let metadata: Value = row.get(1);
+
+    transaction.execute(
+        "UPDATE jobs SET state = 'running', updated_at = now() WHERE id = $1",
+        &[&id],
+    )?;
+    transaction.commit()?;
+
+    let outcome = match from_metadata(&metadata) {
+        Some(job) => job.run(),
+        None => Err("unrecognized job metadata".to_string()),
+    };
+
+    match outcome {
+        Ok(()) => {
+            client.execute(
+                "UPDATE jobs SET state = 'finished', updated_at = now() WHERE id = $1",
+                &[&id],
+            )?;
+        }
+        Err(message) => {
+            client.execute(
+                "UPDATE jobs SET state = 'failed', error_message = $1, updated_at = now() WHERE id = $2",
+                &[&message, &id],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+//handle POST /tasks/:id/process by enqueuing a job rather than doing the
+//work inline
+pub fn handle_process_request(request: &str, pool: &DbPool, owner_id: i32) -> (String, String) {
+    match (get_id(request).parse::<i32>(), pool.get()) {
+        (Ok(task_id), Ok(mut client)) => match client.query_opt(
+            "SELECT id FROM tasks WHERE id = $1 AND owner_id = $2",
+            &[&task_id, &owner_id],
+        ) {
+            Ok(Some(_)) => match enqueue_job(pool, &ProcessTaskJob { task_id }) {
+                Ok(job_id) => (
+                    OK_RESPONSE.to_string(),
+                    serde_json::to_string(&serde_json::json!({ "job_id": job_id })).unwrap(),
+                ),
+                Err(_) => (INTERNAL_ERROR.to_string(), "Internal error".to_string()),
+            },
+            Ok(None) => (NOT_FOUND.to_string(), "Task not found".to_string()),
+            Err(_) => (INTERNAL_ERROR.to_string(), "Internal error".to_string()),
+        },
+        _ => (INTERNAL_ERROR.to_string(), "Internal error".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_metadata_round_trips_through_from_metadata() {
+        let job = ProcessTaskJob { task_id: 7 };
+        let metadata = job.to_metadata();
+        let parsed = from_metadata(&metadata).expect("metadata should parse");
+        assert_eq!(parsed.task_id, 7);
+    }
+
+    #[test]
+    fn from_metadata_rejects_a_missing_task_id() {
+        let metadata = serde_json::json!({ "type": "process_task" });
+        assert!(from_metadata(&metadata).is_none());
+    }
+
+    #[test]
+    fn process_task_job_run_succeeds() {
+        let job = ProcessTaskJob { task_id: 7 };
+        assert!(job.run().is_ok());
+    }
+}
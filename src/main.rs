@@ -1,8 +1,19 @@
-use postgres::Error as PostgresError;
-use postgres::{Client, NoTls};
+use postgres::NoTls;
+use r2d2::Pool;
+use r2d2_postgres::PostgresConnectionManager;
 use std::env;
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+
+mod auth;
+mod cors;
+mod http;
+mod jobs;
+mod migrations;
+mod query;
 
 #[macro_use]
 extern crate serde_derive;
@@ -21,42 +32,83 @@ const DB_URL: &str = env!("DATABASE_URL");
 const OK_RESPONSE: &str = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n";
 const NOT_FOUND: &str = "HTTP/1.1 404 NOT FOUND\r\n\r\n";
 const INTERNAL_ERROR: &str = "HTTP/1.1 500 INTERNAL ERROR\r\n\r\n";
+const UNAUTHORIZED: &str = "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n";
+
+type DbPool = Pool<PostgresConnectionManager<NoTls>>;
+
+//bounds how many connections are served at once so a burst of clients
+//can't exhaust the db pool or the process's file descriptors
+const MAX_CONCURRENT_CONNECTIONS: usize = 100;
+
+//build the pool once at startup, sized from env vars so the server stays
+//responsive under concurrent load instead of reconnecting per request
+fn build_pool() -> DbPool {
+    let manager = PostgresConnectionManager::new(DB_URL.parse().unwrap(), NoTls);
+
+    let min_idle = env::var("DB_POOL_MIN_IDLE")
+        .ok()
+        .and_then(|v| v.parse().ok());
+    let max_size = env::var("DB_POOL_MAX_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(10);
+    let connection_timeout = env::var("DB_POOL_CONNECTION_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30);
+
+    Pool::builder()
+        .min_idle(min_idle)
+        .max_size(max_size)
+        .connection_timeout(Duration::from_secs(connection_timeout))
+        .build(manager)
+        .expect("Failed to build connection pool")
+}
 
-fn main() {
-    //Set Database
-    if let Err(_) = set_database() {
-        println!("Error setting database");
+#[tokio::main]
+async fn main() {
+    let pool = build_pool();
+
+    //run_migrations talks to postgres through the blocking r2d2 pool, so
+    //it runs on a blocking thread rather than tying up the runtime
+    let migrations_pool = pool.clone();
+    let migrations_result = tokio::task::spawn_blocking(move || {
+        migrations::run_migrations(&migrations_pool).map_err(|e| e.to_string())
+    })
+    .await
+    .expect("migrations task panicked");
+
+    if let Err(e) = migrations_result {
+        println!("Error running migrations: {}", e);
         return;
     }
 
+    //background worker that drains the job queue
+    jobs::start_worker(pool.clone());
+
     //start server and print port
-    let listener = TcpListener::bind(format!("0.0.0.0:8080")).unwrap();
+    let listener = TcpListener::bind("0.0.0.0:8080").await.unwrap();
     println!("Server listening on port 8080");
 
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                handle_client(stream);
-            }
+    let connection_limit = Arc::new(Semaphore::new(MAX_CONCURRENT_CONNECTIONS));
+
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(conn) => conn,
             Err(e) => {
                 println!("Unable to connect: {}", e);
+                continue;
             }
-        }
-    }
-}
+        };
 
-fn set_database() -> Result<(), PostgresError> {
-    let mut client = Client::connect(DB_URL, NoTls)?;
-    client.batch_execute(
-        "
-        CREATE TABLE IF NOT EXISTS taskss (
-            id SERIAL PRIMARY KEY,
-            title VARCHAR NOT NULL,
-            description VARCHAR NOT NULL
-        )
-    ",
-    )?;
-    Ok(())
+        let pool = pool.clone();
+        let permit = connection_limit.clone().acquire_owned().await.unwrap();
+
+        tokio::spawn(async move {
+            let _permit = permit;
+            handle_client(stream, pool).await;
+        });
+    }
 }
 
 //Get id from request URL
@@ -74,42 +126,92 @@ fn get_task_request_body(request: &str) -> Result<Task, serde_json::Error> {
     serde_json::from_str(request.split("\r\n\r\n").last().unwrap_or_default())
 }
 
-fn handle_client(mut stream: TcpStream) {
-    let mut buffer = [0; 1024];
-    let mut request = String::new();
-
-    match stream.read(&mut buffer) {
-        Ok(size) => {
-            request.push_str(String::from_utf8_lossy(&buffer[..size]).as_ref());
-
-            let (status_line, content) = match &*request {
-                r if r.starts_with("POST /tasks") => handle_post_request(r),
-                r if r.starts_with("GET /tasks/") => handle_get_request(r),
-                r if r.starts_with("GET /tasks") => handle_get_all_request(r),
-                r if r.starts_with("PUT /tasks/") => handle_put_request(r),
-                r if r.starts_with("DELETE /tasks/") => handle_delete_request(r),
-                _ => (NOT_FOUND.to_string(), "404 not found".to_string()),
+//serve requests off one connection until the client closes it or sends
+//`Connection: close`, instead of handling exactly one request then
+//dropping the socket
+async fn handle_client(mut stream: TcpStream, pool: DbPool) {
+    loop {
+        let raw = match http::read_request(&mut stream).await {
+            Ok(Some(raw)) => raw,
+            Ok(None) => return,
+            Err(e) => {
+                eprintln!("Unable to read stream: {}", e);
+                return;
+            }
+        };
+
+        let keep_alive = raw.keep_alive();
+        let request = raw.as_str();
+
+        //route_request talks to postgres through the blocking r2d2 pool,
+        //so it runs on a blocking thread rather than tying up the runtime
+        let blocking_pool = pool.clone();
+        let (status_line, content) =
+            match tokio::task::spawn_blocking(move || route_request(&request, &blocking_pool))
+                .await
+            {
+                Ok(result) => result,
+                Err(_) => (INTERNAL_ERROR.to_string(), "Internal error".to_string()),
             };
+        let status_line = cors::append_origin_header(&status_line);
 
-            stream
-                .write_all(format!("{}{}", status_line, content).as_bytes())
-                .unwrap();
+        if stream
+            .write_all(format!("{}{}", status_line, content).as_bytes())
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        if !keep_alive {
+            return;
         }
-        Err(e) => eprintln!("Unable to read stream: {}", e),
+    }
+}
+
+//route a parsed request to its handler, authenticating task routes
+//(everything but /register, /login and the OPTIONS preflight) before they run
+fn route_request(request: &str, pool: &DbPool) -> (String, String) {
+    match request {
+        r if r.starts_with("OPTIONS /tasks") => cors::preflight_response(),
+        r if r.starts_with("POST /register") => auth::handle_register_request(r, pool),
+        r if r.starts_with("POST /login") => auth::handle_login_request(r, pool),
+        r if r.starts_with("POST /tasks")
+            || r.starts_with("GET /tasks")
+            || r.starts_with("PUT /tasks")
+            || r.starts_with("DELETE /tasks") =>
+        {
+            match auth::authenticate(r) {
+                Some(claims) => dispatch_task_request(r, pool, claims.user_id),
+                None => (UNAUTHORIZED.to_string(), "Unauthorized".to_string()),
+            }
+        }
+        _ => (NOT_FOUND.to_string(), "404 not found".to_string()),
+    }
+}
+
+fn dispatch_task_request(request: &str, pool: &DbPool, owner_id: i32) -> (String, String) {
+    match request {
+        r if r.starts_with("POST /tasks/") && r.contains("/process") => {
+            jobs::handle_process_request(r, pool, owner_id)
+        }
+        r if r.starts_with("POST /tasks") => handle_post_request(r, pool, owner_id),
+        r if r.starts_with("GET /tasks/") => handle_get_request(r, pool, owner_id),
+        r if r.starts_with("GET /tasks") => handle_get_all_request(r, pool, owner_id),
+        r if r.starts_with("PUT /tasks/") => handle_put_request(r, pool, owner_id),
+        r if r.starts_with("DELETE /tasks/") => handle_delete_request(r, pool, owner_id),
+        _ => (NOT_FOUND.to_string(), "404 not found".to_string()),
     }
 }
 
 //handle post request
-fn handle_post_request(request: &str) -> (String, String) {
-    match (
-        get_task_request_body(&request),
-        Client::connect(DB_URL, NoTls),
-    ) {
+fn handle_post_request(request: &str, pool: &DbPool, owner_id: i32) -> (String, String) {
+    match (get_task_request_body(request), pool.get()) {
         (Ok(task), Ok(mut client)) => {
             client
                 .execute(
-                    "INSERT INTO tasks (title, description) VALUES ($1, $2)",
-                    &[&task.title, &task.description],
+                    "INSERT INTO tasks (title, description, owner_id) VALUES ($1, $2, $3)",
+                    &[&task.title, &task.description, &owner_id],
                 )
                 .unwrap();
 
@@ -120,13 +222,13 @@ fn handle_post_request(request: &str) -> (String, String) {
 }
 
 //handle get request
-fn handle_get_request(request: &str) -> (String, String) {
-    match (
-        get_id(&request).parse::<i32>(),
-        Client::connect(DB_URL, NoTls),
-    ) {
+fn handle_get_request(request: &str, pool: &DbPool, owner_id: i32) -> (String, String) {
+    match (get_id(request).parse::<i32>(), pool.get()) {
         (Ok(id), Ok(mut client)) => {
-            match client.query_one("SELECT * FROM tasks WHERE id = $1", &[&id]) {
+            match client.query_one(
+                "SELECT id, title, description FROM tasks WHERE id = $1 AND owner_id = $2",
+                &[&id, &owner_id],
+            ) {
                 Ok(row) => {
                     let task = Task {
                         id: row.get(0),
@@ -148,43 +250,63 @@ fn handle_get_request(request: &str) -> (String, String) {
 }
 
 //handle get all request
-fn handle_get_all_request(_request: &str) -> (String, String) {
-    match Client::connect(DB_URL, NoTls) {
+fn handle_get_all_request(request: &str, pool: &DbPool, owner_id: i32) -> (String, String) {
+    let params = query::parse_list_params(request);
+
+    match pool.get() {
         Ok(mut client) => {
-            let mut tasks = Vec::new();
+            let (count_sql, count_values) = query::build_count_query(owner_id, &params);
+            let count_params = query::to_sql_params(count_values);
+            let count_refs: Vec<&(dyn postgres::types::ToSql + Sync)> =
+                count_params.iter().map(|v| v.as_ref()).collect();
 
-            for row in client
-                .query("SELECT id, title, description FROM tasks", &[])
-                .unwrap()
-            {
-                tasks.push(Task {
+            let total: i64 = match client.query_one(&count_sql, &count_refs) {
+                Ok(row) => row.get(0),
+                Err(_) => return (INTERNAL_ERROR.to_string(), "Internal error".to_string()),
+            };
+
+            let (list_sql, list_values) = query::build_list_query(owner_id, &params);
+            let list_params = query::to_sql_params(list_values);
+            let list_refs: Vec<&(dyn postgres::types::ToSql + Sync)> =
+                list_params.iter().map(|v| v.as_ref()).collect();
+
+            let rows = match client.query(&list_sql, &list_refs) {
+                Ok(rows) => rows,
+                Err(_) => return (INTERNAL_ERROR.to_string(), "Internal error".to_string()),
+            };
+
+            let tasks: Vec<Task> = rows
+                .iter()
+                .map(|row| Task {
                     id: row.get(0),
                     title: row.get(1),
                     description: row.get(2),
-                });
-            }
+                })
+                .collect();
+
+            let status_line = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nX-Total-Count: {}\r\n\r\n",
+                total
+            );
 
-            (
-                OK_RESPONSE.to_string(),
-                serde_json::to_string(&tasks).unwrap(),
-            )
+            (status_line, serde_json::to_string(&tasks).unwrap())
         }
         _ => (INTERNAL_ERROR.to_string(), "Internal error".to_string()),
     }
 }
 
 //handle put request
-fn handle_put_request(request: &str) -> (String, String) {
+fn handle_put_request(request: &str, pool: &DbPool, owner_id: i32) -> (String, String) {
     match (
-        get_id(&request).parse::<i32>(),
-        get_task_request_body(&request),
-        Client::connect(DB_URL, NoTls),
+        get_id(request).parse::<i32>(),
+        get_task_request_body(request),
+        pool.get(),
     ) {
         (Ok(id), Ok(task), Ok(mut client)) => {
             client
                 .execute(
-                    "UPDATE tasks SET name = $1, email = $2 WHERE id = $3",
-                    &[&task.title, &task.description, &id],
+                    "UPDATE tasks SET title = $1, description = $2 WHERE id = $3 AND owner_id = $4",
+                    &[&task.title, &task.description, &id, &owner_id],
                 )
                 .unwrap();
 
@@ -195,14 +317,14 @@ fn handle_put_request(request: &str) -> (String, String) {
 }
 
 //handle delete request
-fn handle_delete_request(request: &str) -> (String, String) {
-    match (
-        get_id(&request).parse::<i32>(),
-        Client::connect(DB_URL, NoTls),
-    ) {
+fn handle_delete_request(request: &str, pool: &DbPool, owner_id: i32) -> (String, String) {
+    match (get_id(request).parse::<i32>(), pool.get()) {
         (Ok(id), Ok(mut client)) => {
             let rows_affected = client
-                .execute("DELETE FROM tasks WHERE id = $1", &[&id])
+                .execute(
+                    "DELETE FROM tasks WHERE id = $1 AND owner_id = $2",
+                    &[&id, &owner_id],
+                )
                 .unwrap();
 
             //if rows affected is 0, user not found
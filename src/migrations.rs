@@ -0,0 +1,100 @@
+use std::error::Error;
+use std::fs;
+
+use crate::DbPool;
+
+//directory of ordered, versioned .sql migrations, applied at startup
+const MIGRATIONS_DIR: &str = "migrations";
+
+struct Migration {
+    version: String,
+    sql: String,
+}
+
+//apply any migrations that aren't yet recorded in _schema_migrations, in
+//filename order, each inside its own transaction
+pub fn run_migrations(pool: &DbPool) -> Result<(), Box<dyn Error>> {
+    let mut client = pool.get()?;
+
+    client.batch_execute(
+        "CREATE TABLE IF NOT EXISTS _schema_migrations (
+            version VARCHAR PRIMARY KEY,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )",
+    )?;
+
+    for migration in pending_migrations(&mut client)? {
+        println!("Applying migration {}", migration.version);
+
+        let mut transaction = client.transaction()?;
+        transaction.batch_execute(&migration.sql)?;
+        transaction.execute(
+            "INSERT INTO _schema_migrations (version) VALUES ($1)",
+            &[&migration.version],
+        )?;
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+fn pending_migrations(client: &mut postgres::Client) -> Result<Vec<Migration>, Box<dyn Error>> {
+    let applied: Vec<String> = client
+        .query("SELECT version FROM _schema_migrations", &[])?
+        .into_iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    let mut entries: Vec<_> = fs::read_dir(MIGRATIONS_DIR)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "sql"))
+        .collect();
+
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut migrations = Vec::new();
+    for entry in entries {
+        let version = entry.file_name().to_string_lossy().to_string();
+        if applied.contains(&version) {
+            continue;
+        }
+
+        let sql = fs::read_to_string(entry.path())?;
+        migrations.push(Migration { version, sql });
+    }
+
+    Ok(migrations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //pending_migrations needs a live client to know what's applied, but the
+    //directory scan/sort it layers on top is plain filesystem logic we can
+    //exercise directly against the real migrations/ directory
+    #[test]
+    fn migrations_dir_is_applied_in_filename_order() {
+        let mut entries: Vec<_> = fs::read_dir(MIGRATIONS_DIR)
+            .expect("migrations dir should exist")
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "sql"))
+            .collect();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let versions: Vec<String> = entries
+            .iter()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+
+        assert_eq!(
+            versions,
+            vec![
+                "001_create_tasks_table.sql",
+                "002_create_users_table.sql",
+                "003_add_owner_id_to_tasks.sql",
+                "004_create_jobs_table.sql",
+            ]
+        );
+    }
+}
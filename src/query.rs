@@ -0,0 +1,236 @@
+use sea_query::{Expr, Iden, Order, PostgresQueryBuilder, Query, Value, Values};
+use std::collections::HashMap;
+
+//the tasks table and its columns; sortable_column/filterable_column pick
+//which of them callers may sort and filter by. Keeping this as an
+//Iden enum (instead of interpolating raw strings) is what lets the
+//query builder bind everything safely
+#[derive(Iden, Clone, Copy, Debug, PartialEq)]
+enum Tasks {
+    Table,
+    Id,
+    Title,
+    Description,
+    OwnerId,
+}
+
+pub struct ListParams {
+    limit: u64,
+    offset: u64,
+    sort: Tasks,
+    order: Order,
+    contains_filters: Vec<(Tasks, String)>,
+}
+
+fn sortable_column(name: &str) -> Option<Tasks> {
+    match name {
+        "id" => Some(Tasks::Id),
+        "title" => Some(Tasks::Title),
+        "description" => Some(Tasks::Description),
+        _ => None,
+    }
+}
+
+fn filterable_column(name: &str) -> Option<Tasks> {
+    match name {
+        "title" => Some(Tasks::Title),
+        "description" => Some(Tasks::Description),
+        _ => None,
+    }
+}
+
+//pull the query string off the request line, e.g.
+//"GET /tasks?limit=20&sort=title HTTP/1.1"
+fn parse_query_string(request: &str) -> HashMap<String, String> {
+    let path = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default();
+
+    let query = match path.split_once('?') {
+        Some((_, query)) => query,
+        None => return HashMap::new(),
+    };
+
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+//parse limit/offset/sort/order/*_contains off the request line, falling
+//back to sane defaults for anything missing or not whitelisted
+pub fn parse_list_params(request: &str) -> ListParams {
+    let query = parse_query_string(request);
+
+    let limit = query
+        .get("limit")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(50);
+    let offset = query
+        .get("offset")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let sort = query
+        .get("sort")
+        .and_then(|v| sortable_column(v))
+        .unwrap_or(Tasks::Id);
+    let order = match query.get("order").map(|v| v.as_str()) {
+        Some("desc") => Order::Desc,
+        _ => Order::Asc,
+    };
+
+    let contains_filters = query
+        .iter()
+        .filter_map(|(key, value)| {
+            key.strip_suffix("_contains")
+                .and_then(filterable_column)
+                .map(|column| (column, value.clone()))
+        })
+        .collect();
+
+    ListParams {
+        limit,
+        offset,
+        sort,
+        order,
+        contains_filters,
+    }
+}
+
+fn apply_filters(query: &mut sea_query::SelectStatement, params: &ListParams) {
+    for (column, value) in &params.contains_filters {
+        query.and_where(Expr::col(*column).like(format!("%{}%", value)));
+    }
+}
+
+//SELECT id, title, description FROM tasks, scoped to the caller and
+//shaped by the whitelisted sort/filter/pagination params
+pub fn build_list_query(owner_id: i32, params: &ListParams) -> (String, Values) {
+    let mut query = Query::select();
+    query
+        .columns([Tasks::Id, Tasks::Title, Tasks::Description])
+        .from(Tasks::Table)
+        .and_where(Expr::col(Tasks::OwnerId).eq(owner_id))
+        .order_by(params.sort, params.order.clone())
+        .limit(params.limit)
+        .offset(params.offset);
+
+    apply_filters(&mut query, params);
+
+    query.build(PostgresQueryBuilder)
+}
+
+//same filters as build_list_query but as a COUNT(*), for X-Total-Count
+pub fn build_count_query(owner_id: i32, params: &ListParams) -> (String, Values) {
+    let mut query = Query::select();
+    query
+        .expr(Expr::col(Tasks::Id).count())
+        .from(Tasks::Table)
+        .and_where(Expr::col(Tasks::OwnerId).eq(owner_id));
+
+    apply_filters(&mut query, params);
+
+    query.build(PostgresQueryBuilder)
+}
+
+//sea-query hands back its own Value enum; postgres needs concrete ToSql
+//values to bind against the $1, $2, ... placeholders it generated
+pub fn to_sql_params(values: Values) -> Vec<Box<dyn postgres::types::ToSql + Sync>> {
+    values
+        .into_iter()
+        .map(|value| -> Box<dyn postgres::types::ToSql + Sync> {
+            match value {
+                Value::Int(Some(v)) => Box::new(v),
+                Value::BigInt(Some(v)) => Box::new(v),
+                //postgres has no unsigned integer types; limit/offset are
+                //always non-negative, so cast into the signed types the
+                //columns/placeholders actually use
+                Value::Unsigned(Some(v)) => Box::new(v as i32),
+                Value::BigUnsigned(Some(v)) => Box::new(v as i64),
+                Value::String(Some(v)) => Box::new(*v),
+                other => panic!("to_sql_params: unsupported sea-query value variant: {:?}", other),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_list_params_defaults_when_query_string_is_absent() {
+        let params = parse_list_params("GET /tasks HTTP/1.1\r\n\r\n");
+
+        assert_eq!(params.limit, 50);
+        assert_eq!(params.offset, 0);
+        assert_eq!(params.sort, Tasks::Id);
+        assert!(params.contains_filters.is_empty());
+    }
+
+    #[test]
+    fn parse_list_params_reads_whitelisted_params() {
+        let params = parse_list_params(
+            "GET /tasks?limit=20&offset=40&sort=title&order=desc&title_contains=foo HTTP/1.1\r\n\r\n",
+        );
+
+        assert_eq!(params.limit, 20);
+        assert_eq!(params.offset, 40);
+        assert_eq!(params.sort, Tasks::Title);
+        assert_eq!(params.contains_filters, vec![(Tasks::Title, "foo".to_string())]);
+    }
+
+    #[test]
+    fn parse_list_params_ignores_non_whitelisted_sort_and_filter_keys() {
+        let params = parse_list_params(
+            "GET /tasks?sort=password_hash&owner_id_contains=1 HTTP/1.1\r\n\r\n",
+        );
+
+        assert_eq!(params.sort, Tasks::Id);
+        assert!(params.contains_filters.is_empty());
+    }
+
+    #[test]
+    fn build_list_query_scopes_to_owner_and_binds_filters() {
+        let params = parse_list_params("GET /tasks?title_contains=foo HTTP/1.1\r\n\r\n");
+        let (sql, values) = build_list_query(7, &params);
+
+        assert!(sql.contains("\"owner_id\" = $1"));
+        assert!(sql.contains("LIKE $2"));
+        // owner_id, the title_contains filter, limit, and offset
+        assert_eq!(values.0.len(), 4);
+    }
+
+    //build_list_query's default limit/offset bind as sea-query's
+    //Unsigned/BigUnsigned variants; encode them the same way the postgres
+    //wire protocol would and check the bytes are a real int4/int8, not a
+    //stringified number bound as text
+    #[test]
+    fn to_sql_params_binds_unsigned_and_big_unsigned_as_integers_not_text() {
+        use postgres::types::Type;
+
+        let values = Values(vec![Value::BigUnsigned(Some(20)), Value::Unsigned(Some(5))]);
+        let params = to_sql_params(values);
+
+        let mut buf = bytes::BytesMut::new();
+        params[0].to_sql_checked(&Type::INT8, &mut buf).unwrap();
+        assert_eq!(
+            i64::from_be_bytes(buf.as_ref().try_into().unwrap()),
+            20,
+            "BigUnsigned should bind as an 8-byte bigint, not text"
+        );
+
+        buf.clear();
+        params[1].to_sql_checked(&Type::INT4, &mut buf).unwrap();
+        assert_eq!(
+            i32::from_be_bytes(buf.as_ref().try_into().unwrap()),
+            5,
+            "Unsigned should bind as a 4-byte int, not text"
+        );
+    }
+}